@@ -8,11 +8,10 @@
 
 use std::sync::Arc;
 
-use parking_lot::RwLock;
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
-use crate::{Identifier, Interner, ParseError, Span};
+use crate::{Diagnostic, Identifier, Interner, ParseError, Severity, Span};
 
 /// The top of a CVARINFO abstract syntax tree.
 #[derive(Debug, Clone, PartialEq)]
@@ -20,9 +19,125 @@ use crate::{Identifier, Interner, ParseError, Span};
 pub struct CVarInfo(pub Vec<CVar>);
 
 impl CVarInfo {
-	pub fn parse(input: &str, interner: &Arc<RwLock<Interner>>) -> Result<Self, ParseError> {
+	pub fn parse(input: &str, interner: &Arc<Interner>) -> Result<Self, ParseError> {
 		parser::lump(input, interner)
 	}
+
+	/// Equivalent to [`CVarInfo::parse`], but converts a failure into a
+	/// [`Diagnostic`] that can be rendered against `input` instead of a bare
+	/// [`ParseError`].
+	pub fn parse_diagnostic(input: &str, interner: &Arc<Interner>) -> Result<Self, Diagnostic> {
+		Self::parse(input, interner).map_err(Diagnostic::from)
+	}
+
+	/// Runs semantic checks over an already-parsed AST that the grammar alone
+	/// cannot enforce, e.g. an initializer whose [`Value`] variant disagrees
+	/// with the declared [`StorageType`]. Unlike [`CVarInfo::parse`], this
+	/// collects every finding instead of stopping at the first one.
+	#[must_use]
+	pub fn validate(&self) -> Vec<Diagnostic> {
+		self.0.iter().flat_map(CVar::validate).collect()
+	}
+
+	/// Like [`CVarInfo::parse`], but a malformed definition does not prevent
+	/// the rest of `input` from being parsed. `input` is split into segments
+	/// at top-level `;` boundaries — honoring string/color literals and
+	/// line/block comments the same way the grammar does, so a `;` inside
+	/// one of those is not mistaken for a separator — and each segment is
+	/// parsed independently. Segments that parse successfully become `CVar`s
+	/// in the returned [`CVarInfo`]; segments that fail to parse each become
+	/// a [`Diagnostic`] instead of aborting the whole parse.
+	#[must_use]
+	pub fn parse_resilient(input: &str, interner: &Arc<Interner>) -> (Self, Vec<Diagnostic>) {
+		let mut cvars = vec![];
+		let mut diags = vec![];
+
+		for segment in split_top_level(input) {
+			let text = &input[segment.start()..segment.end()];
+
+			match parser::definition(text, interner) {
+				Ok(mut cvar) => {
+					cvar.offset(segment.start());
+					cvars.push(cvar);
+				}
+				Err(err) => diags.push(Diagnostic::from(err).offset(segment.start())),
+			}
+		}
+
+		(Self(cvars), diags)
+	}
+}
+
+/// Splits `input` at top-level `;` boundaries for [`CVarInfo::parse_resilient`],
+/// returning the span of each non-empty segment. A `;` inside a string/color
+/// literal (`"..."`) or inside a `//` or `/* */` comment is not a boundary,
+/// mirroring the same shapes the grammar's `_`, `line_comment`, and
+/// `block_comment` rules recognize.
+fn split_top_level(input: &str) -> Vec<Span> {
+	#[derive(Clone, Copy, PartialEq)]
+	enum State {
+		Normal,
+		LineComment,
+		BlockComment,
+		StringLit,
+	}
+
+	let mut state = State::Normal;
+	let mut spans = vec![];
+	let mut seg_start = 0;
+	let mut seg_has_content = false;
+	let mut chars = input.char_indices().peekable();
+
+	while let Some((i, c)) = chars.next() {
+		match state {
+			State::Normal => match c {
+				'"' => {
+					state = State::StringLit;
+					seg_has_content = true;
+				}
+				'/' if chars.peek().map(|&(_, n)| n) == Some('/') => {
+					chars.next();
+					state = State::LineComment;
+				}
+				'/' if chars.peek().map(|&(_, n)| n) == Some('*') => {
+					chars.next();
+					state = State::BlockComment;
+				}
+				';' => {
+					if seg_has_content {
+						spans.push(Span::new(seg_start, i + 1));
+					}
+
+					seg_start = i + 1;
+					seg_has_content = false;
+				}
+				c if !c.is_whitespace() => seg_has_content = true,
+				_ => {}
+			},
+			State::LineComment => {
+				if c == '\n' {
+					state = State::Normal;
+				}
+			}
+			State::BlockComment => {
+				if c == '*' && chars.peek().map(|&(_, n)| n) == Some('/') {
+					chars.next();
+					state = State::Normal;
+				}
+			}
+			State::StringLit => {
+				if c == '"' {
+					state = State::Normal;
+				}
+			}
+		}
+	}
+
+	if seg_has_content {
+		spans.push(Span::new(seg_start, input.len()));
+	}
+
+	spans
 }
 
 impl std::ops::Deref for CVarInfo {
@@ -50,6 +165,115 @@ pub struct CVar {
 	pub init: Option<Initializer>,
 }
 
+impl CVar {
+	/// Shifts every span belonging to this `CVar` by `by` bytes. Used by
+	/// [`CVarInfo::parse_resilient`] to translate the spans produced while
+	/// parsing a standalone segment back into offsets within the original
+	/// input.
+	fn offset(&mut self, by: usize) {
+		self.span = self.span.offset(by);
+
+		for flag in &mut self.flags {
+			flag.span = flag.span.offset(by);
+		}
+
+		self.type_spec.span = self.type_spec.span.offset(by);
+		self.name.span = self.name.span.offset(by);
+
+		if let Some(init) = &mut self.init {
+			init.span = init.span.offset(by);
+		}
+	}
+
+	/// See [`CVarInfo::validate`].
+	fn validate(&self) -> Vec<Diagnostic> {
+		let mut diags = vec![];
+
+		if let Some(init) = &self.init {
+			if let Some(found) = self.type_spec.storage_type.mismatched_with(&init.value) {
+				diags.push(
+					Diagnostic::new(
+						Severity::Error,
+						init.span,
+						format!(
+							"type mismatch in initializer for CVar `{}`: expected `{}`, found `{found}`",
+							self.name.string,
+							self.type_spec.storage_type.name(),
+						),
+					)
+					.with_label(
+						self.type_spec.span,
+						format!("declared as `{}` here", self.type_spec.storage_type.name()),
+					),
+				);
+			}
+
+			if let Value::Int(i) = init.value {
+				if i < 0 && looks_unsigned(&self.name.string.as_string()) {
+					diags.push(Diagnostic::new(
+						Severity::Warning,
+						init.span,
+						format!(
+							"negative default ({i}) for CVar `{}`, whose name suggests it holds an unsigned value",
+							self.name.string
+						),
+					));
+				}
+			}
+		}
+
+		let mut seen = std::collections::HashSet::new();
+		let mut server_span = None;
+		let mut user_span = None;
+
+		for flag in &self.flags {
+			match flag.kind {
+				FlagKind::Server => {
+					server_span.get_or_insert(flag.span);
+				}
+				FlagKind::User => {
+					user_span.get_or_insert(flag.span);
+				}
+				_ => {}
+			}
+
+			if !seen.insert(flag.kind) {
+				diags.push(Diagnostic::new(
+					Severity::Warning,
+					flag.span,
+					format!("duplicate `{:?}` flag on CVar `{}`", flag.kind, self.name.string),
+				));
+			}
+		}
+
+		if let (Some(server), Some(user)) = (server_span, user_span) {
+			diags.push(
+				Diagnostic::new(
+					Severity::Error,
+					server.combine(user),
+					format!(
+						"CVar `{}` cannot be both `server` and `user` scoped",
+						self.name.string
+					),
+				)
+				.with_label(server, "marked `server` here")
+				.with_label(user, "marked `user` here"),
+			);
+		}
+
+		diags
+	}
+}
+
+/// Returns whether `name` looks, by convention, like it is meant to hold an
+/// unsigned quantity (e.g. a count or a size), for use by [`CVar::validate`].
+fn looks_unsigned(name: &str) -> bool {
+	let lower = name.to_ascii_lowercase();
+	["count", "num", "size", "amount"]
+		.iter()
+		.any(|needle| lower.contains(needle))
+}
+
 /// AST node corresponding to an optional CVar qualifier.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
@@ -60,7 +284,7 @@ pub struct Flag {
 }
 
 /// The semantic component of a [`Flag`].
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum FlagKind {
 	/// Shared between players in a network game and only mutable by the arbitrator.
@@ -100,6 +324,44 @@ pub enum StorageType {
 	Color,
 }
 
+impl StorageType {
+	/// Name used when reporting a [`Diagnostic`] for this type.
+	#[must_use]
+	fn name(&self) -> &'static str {
+		match self {
+			Self::Bool => "bool",
+			Self::Int => "int",
+			Self::Float => "float",
+			Self::String => "string",
+			Self::Color => "color",
+		}
+	}
+
+	/// If `value` cannot be the default for a CVar declared with this storage
+	/// type, returns the name of `value`'s actual type for diagnostic purposes.
+	#[must_use]
+	fn mismatched_with(&self, value: &Value) -> Option<&'static str> {
+		let found = match value {
+			Value::Bool(_) => Self::Bool,
+			Value::Int(_) => Self::Int,
+			Value::Float(_) => Self::Float,
+			Value::String(_) => Self::String,
+			Value::Color { .. } => Self::Color,
+		};
+
+		// `42` is a valid default for a declared `float` just as much as for
+		// a declared `int` (it only fails to round-trip through `f32` in
+		// pathological cases `mismatched_with` doesn't need to worry about),
+		// so an `int` initializer is accepted for a declared `float`. A
+		// `float` initializer for a declared `int` is still a mismatch.
+		if matches!((self, &found), (Self::Float, Self::Int)) {
+			return None;
+		}
+
+		(*self != found).then(|| found.name())
+	}
+}
+
 /// AST node corresponding to the optional default setting for a CVar.
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
@@ -127,7 +389,7 @@ pub enum Value {
 }
 
 peg::parser! {
-	grammar parser(interner: &Arc<RwLock<Interner>>) for str {
+	grammar parser(interner: &Arc<Interner>) for str {
 		// Whitespace, comments ////////////////////////////////////////////////
 
 		rule _
@@ -165,7 +427,7 @@ peg::parser! {
 				CVarInfo(definitions)
 			}
 
-		rule definition() -> CVar
+		pub(super) rule definition() -> CVar
 			= 	start:position!()
 				flags:(flag() ** _) _
 				type_spec:type_spec() _
@@ -260,30 +522,27 @@ peg::parser! {
 		rule init() -> Initializer
 			= 	start:position!()
 				"=" _
-				value:(lit_bool() / lit_float() / lit_int() / lit_color() / lit_string())
+				value:(lit_bool() / lit_number() / lit_color() / lit_string())
 				end:position!()
 			{
 				Initializer { span: Span::new(start, end), value, }
 			}
 
-		rule lit_int() -> Value
-			= string:dec_num_str() {?
-				Ok(Value::Int(string.parse::<i32>().or(Err("32-bit integer"))?))
-			}
-
-		rule lit_float() -> Value
-			= string:$(
-				(dec_num_str() "." dec_num_str()) /
-				(dec_num_str() ".") /
-				dec_num_str()
-			) {?
-				Ok(
-					Value::Float(
-						string.parse::<f32>().or(
-							Err("32-bit floating-point number")
-						)?
-					)
-				)
+		// A single rule covering both `int` and `float` literals, rather than
+		// one alternative apiece: PEG's ordered choice commits to the first
+		// alternative that matches a prefix of the input, so a plain digit
+		// string like `"1"` would never reach a separate float alternative
+		// tried second, and a separate int alternative tried first would
+		// swallow just the integer part of `"1.5"` and strand the rest.
+		// Branching on the presence of a `.` only after the whole numeral has
+		// matched sidesteps both problems.
+		rule lit_number() -> Value
+			= string:$("-"? dec_num_str() ("." dec_num_str()?)?) {?
+				if string.contains('.') {
+					Ok(Value::Float(string.parse::<f32>().or(Err("32-bit floating-point number"))?))
+				} else {
+					Ok(Value::Int(string.parse::<i32>().or(Err("32-bit integer"))?))
+				}
 			}
 
 		rule dec_num_str() -> &'input str = $(['0'..='9']+)
@@ -383,4 +642,86 @@ KatanaZERO
 			panic!("Test case [2]'s initializer failed to get parsed.");
 		}
 	}
+
+	#[test]
+	fn validate_type_mismatch() {
+		let interner = Interner::new_arc();
+		let cvarinfo = CVarInfo::parse("int x = true;", &interner).unwrap();
+		let diags = cvarinfo.validate();
+
+		assert_eq!(diags.len(), 1, "Expected exactly 1 diagnostic, got {diags:?}.");
+		assert_eq!(diags[0].severity, Severity::Error);
+		assert!(diags[0].message.contains("bool"));
+	}
+
+	#[test]
+	fn validate_negative_default_for_unsigned_looking_name() {
+		let interner = Interner::new_arc();
+		let cvarinfo = CVarInfo::parse("int item_count = -1;", &interner).unwrap();
+		let diags = cvarinfo.validate();
+
+		assert_eq!(diags.len(), 1, "Expected exactly 1 diagnostic, got {diags:?}.");
+		assert_eq!(diags[0].severity, Severity::Warning);
+		assert!(diags[0].message.contains("negative default"));
+	}
+
+	#[test]
+	fn validate_scope_contradiction_and_duplicate_flag() {
+		let interner = Interner::new_arc();
+		let cvarinfo =
+			CVarInfo::parse("server user cheat cheat bool x = true;", &interner).unwrap();
+		let diags = cvarinfo.validate();
+
+		assert_eq!(diags.len(), 2, "Expected exactly 2 diagnostics, got {diags:?}.");
+		let scope_diag = diags
+			.iter()
+			.find(|d| d.severity == Severity::Error && d.message.contains("server"))
+			.unwrap();
+		// The combined span must start at `server`, not collapse to `user`'s
+		// span just because `user` happens to start later in the source.
+		assert_eq!(scope_diag.primary.start(), 0);
+		assert!(diags
+			.iter()
+			.any(|d| d.severity == Severity::Warning && d.message.contains("duplicate")));
+	}
+
+	#[test]
+	fn parse_resilient_recovers_from_one_bad_definition() {
+		const SOURCE: &str = r#"
+int good_one = 1;
+not a valid cvar at all;
+int good_two = 2;
+"#;
+
+		let interner = Interner::new_arc();
+		let (cvarinfo, diags) = CVarInfo::parse_resilient(SOURCE, &interner);
+
+		assert_eq!(
+			cvarinfo.len(),
+			2,
+			"Expected 2 valid CVar definitions, read {}.",
+			cvarinfo.len()
+		);
+		assert_eq!(diags.len(), 1, "Expected exactly 1 diagnostic, got {diags:?}.");
+	}
+
+	#[test]
+	fn parse_resilient_ignores_semicolons_in_strings_and_comments() {
+		const SOURCE: &str = r#"
+string motd = "welcome; enjoy your stay"; // a comment; with a semicolon
+/* another comment; with one too */
+int x = 1;
+"#;
+
+		let interner = Interner::new_arc();
+		let (cvarinfo, diags) = CVarInfo::parse_resilient(SOURCE, &interner);
+
+		assert!(diags.is_empty(), "Expected no diagnostics, got {diags:?}.");
+		assert_eq!(
+			cvarinfo.len(),
+			2,
+			"Expected 2 valid CVar definitions, read {}.",
+			cvarinfo.len()
+		);
+	}
 }
@@ -0,0 +1,189 @@
+//! Crate-wide diagnostics.
+//!
+//! The grammars in this crate are built with [`peg`], whose generated parsers
+//! only report a byte offset and a set of expected tokens on failure. That is
+//! enough to drive a `Result`, but not enough to show a user *where* in their
+//! source the problem is. [`Diagnostic`] carries one or more [`Span`]s instead
+//! of a bare offset, and [`Diagnostic::render`] turns those spans into the
+//! offending source line with a caret underline beneath it, the way a
+//! compiler front end would.
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::Span;
+
+/// A single finding from a parser or a post-parse validation pass, carrying
+/// enough location information to be rendered against the original source
+/// rather than a bare byte offset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub message: String,
+	/// The span the diagnostic is primarily concerned with.
+	pub primary: Span,
+	/// Secondary spans, each annotated with its own short message.
+	pub labels: Vec<(Span, String)>,
+}
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+impl Diagnostic {
+	#[must_use]
+	pub fn new(severity: Severity, primary: Span, message: impl Into<String>) -> Self {
+		Self {
+			severity,
+			message: message.into(),
+			primary,
+			labels: vec![],
+		}
+	}
+
+	#[must_use]
+	pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+		self.labels.push((span, message.into()));
+		self
+	}
+
+	/// Shifts every span in this diagnostic by `by` bytes; useful when the
+	/// diagnostic was raised against a substring of a larger source and needs
+	/// to be reported in terms of that larger source instead.
+	#[must_use]
+	pub fn offset(mut self, by: usize) -> Self {
+		self.primary = self.primary.offset(by);
+
+		for (span, _) in &mut self.labels {
+			*span = span.offset(by);
+		}
+
+		self
+	}
+
+	/// Renders this diagnostic against the `source` it was raised from,
+	/// showing the line(s) the [`Span`]s point into with a caret underline
+	/// beneath each one.
+	#[must_use]
+	pub fn render(&self, source: &str) -> String {
+		let severity = match self.severity {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+		};
+
+		let mut out = format!("{severity}: {}\n", self.message);
+		render_span(&mut out, source, self.primary, None);
+
+		for (span, label) in &self.labels {
+			render_span(&mut out, source, *span, Some(label));
+		}
+
+		out
+	}
+}
+
+/// A 1-based line/column location within a source string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+	pub line: usize,
+	pub column: usize,
+}
+
+impl LineCol {
+	/// Converts a byte offset into `source` to a 1-based line/column pair,
+	/// counting UTF-8 character boundaries rather than raw bytes.
+	#[must_use]
+	pub fn from_offset(source: &str, offset: usize) -> Self {
+		debug_assert!(
+			source.get(..offset).is_some(),
+			"offset {offset} does not lie on a UTF-8 character boundary"
+		);
+
+		let mut line = 1;
+		let mut column = 1;
+
+		for ch in source[..offset].chars() {
+			if ch == '\n' {
+				line += 1;
+				column = 1;
+			} else {
+				column += 1;
+			}
+		}
+
+		Self { line, column }
+	}
+}
+
+fn render_span(out: &mut String, source: &str, span: Span, label: Option<&str>) {
+	debug_assert!(
+		span.validate(source),
+		"span {span:?} does not lie on a UTF-8 character boundary"
+	);
+
+	let start = LineCol::from_offset(source, span.start());
+	let line_start = source[..span.start()].rfind('\n').map_or(0, |i| i + 1);
+	let line_end = source[span.start()..]
+		.find('\n')
+		.map_or(source.len(), |i| span.start() + i);
+	let line_text = &source[line_start..line_end];
+	let underline_len = span.end().min(line_end).saturating_sub(span.start()).max(1);
+
+	out.push_str(&format!(" --> line {}, column {}\n", start.line, start.column));
+	out.push_str(&format!("  | {line_text}\n"));
+	out.push_str("  | ");
+	out.push_str(&" ".repeat(start.column - 1));
+	out.push_str(&"^".repeat(underline_len));
+
+	if let Some(label) = label {
+		out.push(' ');
+		out.push_str(label);
+	}
+
+	out.push('\n');
+}
+
+impl From<crate::ParseError> for Diagnostic {
+	fn from(err: crate::ParseError) -> Self {
+		let offset = err.location.offset;
+		let message = format!("expected {}", err.expected);
+		Self::new(Severity::Error, Span::new(offset, offset), message)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn render_points_at_offset() {
+		const SOURCE: &str = "int x = true;\nfloat y = 1;";
+
+		let diag = Diagnostic::new(
+			Severity::Error,
+			Span::new(8, 12),
+			"type mismatch: expected `int`, found `bool`",
+		);
+
+		let rendered = diag.render(SOURCE);
+
+		assert!(rendered.contains("line 1, column 9"));
+		assert!(rendered.contains("int x = true;"));
+		assert!(rendered.contains("^^^^"));
+	}
+
+	#[test]
+	fn from_parse_error() {
+		let interner = crate::Interner::new_arc();
+		let err = crate::cvarinfo::CVarInfo::parse("int 42 = 1;", &interner).unwrap_err();
+		let diag = Diagnostic::from(err);
+
+		assert_eq!(diag.severity, Severity::Error);
+		assert!(diag.message.contains("expected"));
+	}
+}
@@ -0,0 +1,156 @@
+//! Graphviz [DOT](https://graphviz.org/doc/info/lang.html) export of parsed
+//! lump ASTs.
+//!
+//! This is a debugging and tooling aid, not part of the core parsing API, so
+//! it is gated behind the `dot` feature. It lets a CVARINFO or LOADACS lump
+//! be visualized as a graph, and is handy for diffing AST shapes in tests.
+
+use std::fmt::Write;
+
+use crate::cvarinfo::{CVar, CVarInfo};
+use crate::loadacs::LoadAcs;
+
+/// Whether a DOT graph's edges are directed (`->`) or undirected (`--`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+	Digraph,
+	Graph,
+}
+
+impl Kind {
+	#[must_use]
+	fn keyword(self) -> &'static str {
+		match self {
+			Self::Digraph => "digraph",
+			Self::Graph => "graph",
+		}
+	}
+
+	#[must_use]
+	pub fn edgeop(self) -> &'static str {
+		match self {
+			Self::Digraph => "->",
+			Self::Graph => "--",
+		}
+	}
+}
+
+/// Accumulates `node`/edge statements and hands out unique node IDs while
+/// walking an AST.
+struct Writer {
+	kind: Kind,
+	out: String,
+	next_id: usize,
+}
+
+impl Writer {
+	fn new(kind: Kind, name: &str) -> Self {
+		let mut out = String::new();
+		writeln!(out, "{} {name} {{", kind.keyword()).unwrap();
+		Self {
+			kind,
+			out,
+			next_id: 0,
+		}
+	}
+
+	fn node(&mut self, label: &str) -> usize {
+		let id = self.next_id;
+		self.next_id += 1;
+		writeln!(self.out, "\tn{id} [label={label:?}];").unwrap();
+		id
+	}
+
+	fn edge(&mut self, from: usize, to: usize) {
+		let edgeop = self.kind.edgeop();
+		writeln!(self.out, "\tn{from} {edgeop} n{to};").unwrap();
+	}
+
+	fn finish(mut self) -> String {
+		self.out.push_str("}\n");
+		self.out
+	}
+}
+
+impl CVarInfo {
+	/// Serializes this AST to Graphviz DOT, for visualizing how a CVARINFO
+	/// lump was parsed.
+	#[must_use]
+	pub fn to_dot(&self, kind: Kind) -> String {
+		let mut writer = Writer::new(kind, "cvarinfo");
+		let root = writer.node("CVarInfo");
+
+		for cvar in self.iter() {
+			let cvar_id = cvar.write_dot(&mut writer);
+			writer.edge(root, cvar_id);
+		}
+
+		writer.finish()
+	}
+}
+
+impl CVar {
+	fn write_dot(&self, writer: &mut Writer) -> usize {
+		let id = writer.node("CVar");
+
+		for flag in &self.flags {
+			let flag_id = writer.node(&format!("{:?}", flag.kind));
+			writer.edge(id, flag_id);
+		}
+
+		let type_id = writer.node(&format!("{:?}", self.type_spec.storage_type));
+		writer.edge(id, type_id);
+
+		let name_id = writer.node(&self.name.string.as_string());
+		writer.edge(id, name_id);
+
+		if let Some(init) = &self.init {
+			let init_id = writer.node(&format!("{:?}", init.value));
+			writer.edge(id, init_id);
+		}
+
+		id
+	}
+}
+
+impl LoadAcs {
+	/// Serializes this AST to Graphviz DOT.
+	#[must_use]
+	pub fn to_dot(&self, kind: Kind) -> String {
+		let mut writer = Writer::new(kind, "loadacs");
+		let root = writer.node("LoadAcs");
+
+		for object in &self.objects {
+			let object_id = writer.node(object);
+			writer.edge(root, object_id);
+		}
+
+		writer.finish()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn cvarinfo_to_dot() {
+		let interner = crate::Interner::new_arc();
+		let cvarinfo = CVarInfo::parse("server int health = 100;", &interner).unwrap();
+
+		let dot = cvarinfo.to_dot(Kind::Digraph);
+
+		assert!(dot.starts_with("digraph cvarinfo {"));
+		assert!(dot.contains("\"health\""));
+		assert!(dot.contains("->"));
+	}
+
+	#[test]
+	fn loadacs_to_dot() {
+		let loadacs = LoadAcs::parse("FOO.O BAR.O");
+		let dot = loadacs.to_dot(Kind::Graph);
+
+		assert!(dot.contains("--"));
+		assert!(dot.contains("\"FOO.O\""));
+	}
+}
@@ -10,13 +10,18 @@
 //! entry, or some other compressed archive entry.
 
 pub mod cvarinfo;
+pub mod diagnostic;
+#[cfg(feature = "dot")]
+pub mod dot;
 pub mod loadacs;
 
+pub use diagnostic::{Diagnostic, Severity};
+
 pub type ParseError = peg::error::ParseError<<str as peg::Parse>::PositionRepr>;
 
 use std::{hash::Hash, sync::Arc};
 
-use indexmap::IndexSet;
+use indexmap::IndexMap;
 use parking_lot::RwLock;
 #[cfg(feature = "serde")]
 use serde::Serialize;
@@ -45,7 +50,7 @@ impl Span {
 	#[inline(always)]
 	pub fn combine(self, other: Self) -> Self {
 		Self {
-			start: self.start.max(other.start),
+			start: self.start.min(other.start),
 			end: self.end.max(other.end),
 		}
 	}
@@ -59,6 +64,16 @@ impl Span {
 	pub fn end(&self) -> usize {
 		self.end
 	}
+
+	/// Shifts both ends of this span by `by` bytes; useful when re-basing a
+	/// span produced against a substring back onto the original source.
+	#[must_use]
+	pub fn offset(self, by: usize) -> Self {
+		Self {
+			start: self.start + by,
+			end: self.end + by,
+		}
+	}
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -81,7 +96,7 @@ pub struct StringIndex(usize);
 #[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct StringHandle {
 	#[cfg_attr(feature = "serde", serde(skip))]
-	interner: Arc<RwLock<Interner>>,
+	interner: Arc<Interner>,
 	index: StringIndex,
 }
 
@@ -95,13 +110,16 @@ impl Eq for StringHandle {}
 
 impl Hash for StringHandle {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-		self.interner.read().get(self.index).hash(state);
+		// The stored hash need not match what hashing the string bytes
+		// directly would produce; it only has to be stable for this index,
+		// which it is since it was computed once at insertion time.
+		state.write_u64(self.interner.hash_of(self.index));
 	}
 }
 
 impl std::fmt::Display for StringHandle {
 	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		write!(f, "{:?}", self.interner.read().get(self.index))
+		self.interner.with_str(self.index, |s| write!(f, "{s:?}"))
 	}
 }
 
@@ -114,79 +132,289 @@ impl std::fmt::Debug for StringHandle {
 	}
 }
 
-#[derive(Debug, Default)]
-pub struct Interner {
-	set: IndexSet<Box<str>>,
+impl StringHandle {
+	/// Clones the interned string's content out of the interner.
+	#[must_use]
+	pub fn as_string(&self) -> String {
+		self.interner.with_str(self.index, str::to_string)
+	}
+
+	/// Rebinds this handle to `frozen`, a snapshot of the same interner taken
+	/// by [`Interner::freeze`]. Returns `None` if this handle was interned
+	/// *after* `frozen` was taken, since that entry simply isn't present in
+	/// the older snapshot's table; callers that know their handles all
+	/// predate the snapshot may still `.unwrap()` this safely.
+	#[must_use]
+	pub fn freeze(&self, frozen: &Arc<FrozenInterner>) -> Option<FrozenHandle> {
+		let index = self.interner.with_str(self.index, |s| frozen.try_lookup(s))?;
+
+		Some(FrozenHandle {
+			interner: frozen.clone(),
+			index,
+		})
+	}
 }
 
-impl std::fmt::Display for Interner {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		writeln!(f, "{{")?;
+/// The lock-free counterpart to [`StringHandle`], bound to a [`FrozenInterner`]
+/// rather than a growable [`Interner`]. Obtained via [`StringHandle::freeze`]
+/// once parsing is done; this is the type to reach for when handles need to
+/// be stored in a hash map or compared in a hot loop, since none of its
+/// operations ever take a lock.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FrozenHandle {
+	#[cfg_attr(feature = "serde", serde(skip))]
+	interner: Arc<FrozenInterner>,
+	index: StringIndex,
+}
 
-		for (i, s) in self.set.iter().enumerate() {
-			writeln!(f, "\t{} => {:?},", i, s)?;
-		}
+impl PartialEq for FrozenHandle {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.interner, &other.interner) && self.index == other.index
+	}
+}
 
-		write!(f, "}}")?;
+impl Eq for FrozenHandle {}
 
-		Ok(())
+impl Hash for FrozenHandle {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		state.write_u64(self.interner.table.hash(self.index));
 	}
 }
 
-impl Interner {
+impl std::fmt::Display for FrozenHandle {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{:?}", self.interner.table.get(self.index))
+	}
+}
+
+impl std::fmt::Debug for FrozenHandle {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("FrozenHandle")
+			.field("index", &self.index)
+			.finish()
+	}
+}
+
+impl FrozenHandle {
+	/// Clones the interned string's content out of the frozen interner.
 	#[must_use]
-	#[allow(unused)]
-	pub fn new_arc() -> Arc<RwLock<Self>> {
-		Arc::new(RwLock::new(Self::default()))
+	pub fn as_string(&self) -> String {
+		self.interner.table.get(self.index).to_string()
+	}
+}
+
+/// The backing table of an [`Interner`]: each entry is a string paired with
+/// its hash, computed once in [`Table::add`] rather than on every
+/// [`StringHandle`] operation.
+#[derive(Debug, Clone, Default)]
+struct Table(IndexMap<Box<str>, u64>);
+
+impl Table {
+	fn add(&mut self, string: &str) -> StringIndex {
+		let hash = hash_of_str(string);
+		StringIndex(self.0.insert_full(string.to_string().into_boxed_str(), hash).0)
+	}
+
+	fn get(&self, index: StringIndex) -> &str {
+		self.0
+			.get_index(index.0)
+			.expect("`StringIndex` from a different `Interner`")
+			.0
+	}
+
+	fn hash(&self, index: StringIndex) -> u64 {
+		*self
+			.0
+			.get_index(index.0)
+			.expect("`StringIndex` from a different `Interner`")
+			.1
 	}
 
+	fn try_lookup(&self, string: &str) -> Option<StringIndex> {
+		self.0.get_index_of(string).map(StringIndex)
+	}
+}
+
+fn hash_of_str(string: &str) -> u64 {
+	use std::hash::Hasher;
+
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	string.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Interns strings and identifiers, handing out cheaply-cloneable
+/// [`StringHandle`]s in return. The [`StringHandle`] operations that read the
+/// table (`Hash`, `Display`, `as_string`) take a read lock to fetch the
+/// per-entry hash or bytes, though that hash is already precomputed by [`Table::add`]
+/// rather than recomputed on every access.
+///
+/// [`Interner::freeze`] snapshots the current table into an immutable,
+/// unlocked [`FrozenInterner`] without disturbing this interner or any
+/// [`StringHandle`]s already bound to it; rebind those handles to the
+/// snapshot with [`StringHandle::freeze`] to make their operations lock-free
+/// too, which matters for handles stored in hash maps or compared in hot
+/// loops across threads. Rebinding fails (returns `None`) for any handle
+/// interned after the snapshot was taken, since the snapshot's table simply
+/// has no entry for it.
+#[derive(Debug, Default)]
+pub struct Interner {
+	mutable: RwLock<Table>,
+}
+
+impl std::fmt::Display for Interner {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write_table(f, &self.mutable.read())
+	}
+}
+
+impl Interner {
 	#[must_use]
-	pub fn add(&mut self, string: &str) -> StringIndex {
-		StringIndex(self.set.insert_full(string.to_string().into_boxed_str()).0)
+	pub fn new_arc() -> Arc<Self> {
+		Arc::new(Self::default())
 	}
 
-	pub fn intern(this: &Arc<RwLock<Interner>>, string: &str) -> StringHandle {
+	pub fn intern(this: &Arc<Interner>, string: &str) -> StringHandle {
 		{
-			let guard = this.read();
+			let guard = this.mutable.read();
 
-			if let Some(s) = guard.try_lookup(string) {
+			if let Some(index) = guard.try_lookup(string) {
 				return StringHandle {
 					interner: this.clone(),
-					index: s,
+					index,
 				};
 			}
 		}
 
-		{
-			let mut guard = this.write();
+		let mut guard = this.mutable.write();
 
-			StringHandle {
-				interner: this.clone(),
-				index: guard.add(string),
-			}
+		StringHandle {
+			interner: this.clone(),
+			index: guard.add(string),
 		}
 	}
 
+	/// Clones this interner's current contents into an immutable,
+	/// unlocked [`FrozenInterner`]. Entries added after this call are
+	/// naturally absent from the snapshot, and do not affect it.
 	#[must_use]
-	pub fn get(&self, index: StringIndex) -> &str {
-		self.set[index.0].as_ref()
+	pub fn freeze(this: &Arc<Interner>) -> Arc<FrozenInterner> {
+		Arc::new(FrozenInterner {
+			table: this.mutable.read().clone(),
+		})
+	}
+
+	fn with_str<R>(&self, index: StringIndex, f: impl FnOnce(&str) -> R) -> R {
+		f(self.mutable.read().get(index))
+	}
+
+	fn hash_of(&self, index: StringIndex) -> u64 {
+		self.mutable.read().hash(index)
 	}
 
 	#[must_use]
-	pub fn _lookup(&mut self, string: &str) -> StringIndex {
-		if let Some(index) = self.set.get_index_of(string) {
-			StringIndex(index)
-		} else {
-			self.add(string)
-		}
+	pub fn try_lookup(&self, string: &str) -> Option<StringIndex> {
+		self.mutable.read().try_lookup(string)
 	}
+}
+
+/// An immutable, unlocked snapshot of an [`Interner`]'s table, produced by
+/// [`Interner::freeze`]. [`FrozenHandle`] reads go straight through this with
+/// no locking at all.
+#[derive(Debug, Default)]
+pub struct FrozenInterner {
+	table: Table,
+}
+
+impl std::fmt::Display for FrozenInterner {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write_table(f, &self.table)
+	}
+}
 
+impl FrozenInterner {
 	#[must_use]
 	pub fn try_lookup(&self, string: &str) -> Option<StringIndex> {
-		self.set.get_index_of(string).map(StringIndex)
+		self.table.try_lookup(string)
 	}
+}
+
+fn write_table(f: &mut std::fmt::Formatter, table: &Table) -> std::fmt::Result {
+	writeln!(f, "{{")?;
+
+	for (i, (s, _)) in table.0.iter().enumerate() {
+		writeln!(f, "\t{} => {:?},", i, s)?;
+	}
+
+	write!(f, "}}")
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn span_combine_takes_the_union_regardless_of_argument_order() {
+		let earlier = Span::new(0, 6);
+		let later = Span::new(7, 11);
+
+		assert_eq!(earlier.combine(later), Span::new(0, 11));
+		assert_eq!(later.combine(earlier), Span::new(0, 11));
+	}
+
+	#[test]
+	fn freeze_snapshot_is_independent_of_further_mutation() {
+		let interner = Interner::new_arc();
+		let a = Interner::intern(&interner, "health");
+		let b = Interner::intern(&interner, "health");
+
+		assert_eq!(a, b);
+
+		let frozen = Interner::freeze(&interner);
+		assert_eq!(frozen.try_lookup("health"), Some(b.index));
+		assert_eq!(frozen.try_lookup("armor"), None);
+
+		// Interning a new string afterwards still works on the original,
+		// unfrozen interner, and has no effect on the snapshot already taken.
+		Interner::intern(&interner, "armor");
+		assert_eq!(frozen.try_lookup("armor"), None);
+	}
+
+	#[test]
+	fn frozen_handle_preserves_content_and_equality() {
+		let interner = Interner::new_arc();
+		let a = Interner::intern(&interner, "health");
+
+		let frozen = Interner::freeze(&interner);
+		let a_frozen = a.freeze(&frozen).unwrap();
+
+		assert_eq!(a_frozen.to_string(), "\"health\"");
+		assert_eq!(a_frozen.as_string(), "health");
+
+		let b_frozen = Interner::intern(&interner, "health").freeze(&frozen).unwrap();
+		assert_eq!(a_frozen, b_frozen);
+	}
+
+	#[test]
+	fn frozen_handle_usable_as_hash_set_member() {
+		let interner = Interner::new_arc();
+		let a = Interner::intern(&interner, "health");
+		let frozen = Interner::freeze(&interner);
+
+		let mut set = std::collections::HashSet::new();
+		set.insert(a.freeze(&frozen).unwrap());
+
+		let b = Interner::intern(&interner, "health");
+		assert!(set.contains(&b.freeze(&frozen).unwrap()));
+	}
+
+	#[test]
+	fn freeze_handle_fails_for_entry_added_after_snapshot() {
+		let interner = Interner::new_arc();
+		let frozen = Interner::freeze(&interner);
 
-	pub fn _clear(&mut self) {
-		self.set.clear();
+		let armor = Interner::intern(&interner, "armor");
+		assert!(armor.freeze(&frozen).is_none());
 	}
 }
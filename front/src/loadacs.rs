@@ -3,6 +3,8 @@
 //! Parsing these is as simple as breaking up an input string into
 //! whitespace-separated ASCII words.
 
+use crate::Diagnostic;
+
 /// Represents a [`LOADACS`](https://zdoom.org/wiki/LOADACS) lump; contains
 /// a list of bytecode object file names for the engine to load.
 pub struct LoadAcs {
@@ -18,4 +20,12 @@ impl LoadAcs {
 				.collect(),
 		}
 	}
+
+	/// Equivalent to [`LoadAcs::parse`], kept for API symmetry with lump
+	/// frontends like [`CVarInfo::parse_diagnostic`](crate::cvarinfo::CVarInfo::parse_diagnostic)
+	/// that can actually fail; splitting on whitespace never does, so this
+	/// always succeeds.
+	pub fn parse_diagnostic(string: &str) -> Result<Self, Diagnostic> {
+		Ok(Self::parse(string))
+	}
 }